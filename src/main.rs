@@ -1,8 +1,9 @@
 #![allow(warnings)]
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, Read, Write};
 use std::net::IpAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
@@ -12,21 +13,32 @@ use clap::Parser;
 use futures_util::stream::{Stream, StreamExt};
 use local_ip_address::local_ip;
 use notify::Watcher;
-use pulldown_cmark::{html, CowStr, Event, Options, Parser as MdParser};
+use once_cell::sync::Lazy;
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser as MdParser, Tag};
 use pulldown_latex::config::DisplayMode;
 use pulldown_latex::push_mathml;
+use serde::Deserialize;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{styled_line_to_highlighted_html, IncludeBackground};
+use syntect::parsing::SyntaxSet;
 use tokio::sync::{broadcast, RwLock};
 use warp::{sse, Filter};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// A markdown file to preview, or a directory to browse all markdown
+    /// files within it. Directories are only supported in server mode, not
+    /// with `--static-mode`.
     #[arg(value_name = "FILE")]
     file: Option<PathBuf>,
 
     #[arg(short = 'H', long = "host", default_value = "127.0.0.1")]
     host: String,
 
+    /// Port to serve on. If it's already taken, the next free port is used
+    /// instead. Pass 0 to let the OS choose one.
     #[arg(short = 'P', long = "port", default_value = "3030")]
     port: u16,
 
@@ -48,7 +60,7 @@ async fn main() -> io::Result<()> {
 }
 
 fn run_static_mode(args: &Args) -> io::Result<()> {
-    let (file_name, markdown_input) = match &args.file {
+    let (file_name, markdown_input, doc_dir) = match &args.file {
         Some(file_path) => {
             let mut file = File::open(&file_path).unwrap_or_else(|err| {
                 eprintln!("Error opening file {}: {}", file_path.display(), err);
@@ -59,19 +71,21 @@ fn run_static_mode(args: &Args) -> io::Result<()> {
             (
                 file_path.file_name().unwrap().to_string_lossy().to_string(),
                 content,
+                file_path.parent().unwrap_or(Path::new(".")).to_path_buf(),
             )
         }
         None => {
             let mut content = String::new();
             io::stdin().read_to_string(&mut content)?;
-            (String::from("New file"), content)
+            (String::from("New file"), content, PathBuf::from("."))
         }
     };
 
-    let html_output = render_markdown_to_html(&markdown_input);
-    let style = read_style_css();
+    let (front_matter, html_output) = render_document(&markdown_input);
+    let title = resolve_title(&front_matter, &file_name);
+    let style = resolve_style(&read_style_css(), &front_matter, &doc_dir);
     let fonts = read_fonts();
-    let html_content = build_full_html(&file_name, &html_output, &style, &fonts, false);
+    let html_content = build_full_html(&title, &html_output, &style, &fonts, false);
 
     let temp_file = tempfile::Builder::new()
         .prefix("markdown_preview_")
@@ -137,11 +151,80 @@ fn open_in_browser(link: String) {
 
 type EventStream = Pin<Box<dyn Stream<Item = Result<sse::Event, warp::Error>> + Send>>;
 
-fn event_stream(rx: broadcast::Receiver<()>) -> EventStream {
+/// Above this size we stop pushing the freshly rendered body through SSE
+/// (base64 over the wire on every keystroke gets expensive) and just ask the
+/// page to reload itself instead.
+const DOM_PATCH_SIZE_LIMIT: usize = 200_000;
+
+/// What `watch_markdown_file` tells connected clients to do once a file
+/// change has been re-rendered.
+#[derive(Clone)]
+enum ReloadEvent {
+    /// Splice `middle` (base64-encoded) between the first `prefix` and last
+    /// `suffix` bytes of the client's current body, rather than resending
+    /// the whole document on every keystroke.
+    Diff {
+        prefix: usize,
+        suffix: usize,
+        middle: String,
+    },
+    /// The new rendered body is larger than `DOM_PATCH_SIZE_LIMIT`; just
+    /// reload the page.
+    Reload,
+}
+
+/// Lengths of the longest common prefix and (non-overlapping) suffix shared
+/// by `old` and `new`, both clamped to UTF-8 char boundaries so callers can
+/// safely slice on them.
+fn common_prefix_suffix(old: &str, new: &str) -> (usize, usize) {
+    let old_b = old.as_bytes();
+    let new_b = new.as_bytes();
+    let max_common = old_b.len().min(new_b.len());
+
+    let mut prefix = 0;
+    while prefix < max_common && old_b[prefix] == new_b[prefix] {
+        prefix += 1;
+    }
+    while prefix > 0 && !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    let max_suffix = max_common - prefix;
+    let mut suffix = 0;
+    while suffix < max_suffix && old_b[old_b.len() - 1 - suffix] == new_b[new_b.len() - 1 - suffix] {
+        suffix += 1;
+    }
+    while suffix > 0 && !new.is_char_boundary(new_b.len() - suffix) {
+        suffix -= 1;
+    }
+
+    (prefix, suffix)
+}
+
+fn diff_event(old: &str, new: &str) -> ReloadEvent {
+    let (prefix, suffix) = common_prefix_suffix(old, new);
+    let middle = encode(&new.as_bytes()[prefix..new.len() - suffix]);
+    ReloadEvent::Diff {
+        prefix,
+        suffix,
+        middle,
+    }
+}
+
+fn event_stream(rx: broadcast::Receiver<ReloadEvent>) -> EventStream {
     let stream = async_stream::stream! {
         let mut rx = rx;
-        while let Ok(_) = rx.recv().await {
-            yield Ok(sse::Event::default().data("reload"));
+        while let Ok(event) = rx.recv().await {
+            let sse_event = match event {
+                ReloadEvent::Diff { prefix, suffix, middle } => sse::Event::default()
+                    .event("patch")
+                    .data(format!(
+                        "{{\"prefix\":{},\"suffix\":{},\"middle\":\"{}\"}}",
+                        prefix, suffix, middle
+                    )),
+                ReloadEvent::Reload => sse::Event::default().event("reload").data("reload"),
+            };
+            yield Ok(sse_event);
         }
     };
     Box::pin(stream)
@@ -155,19 +238,25 @@ async fn run_server_mode(args: &Args) -> io::Result<()> {
             std::process::exit(1);
         }
     };
+    if file_path.is_dir() {
+        return run_directory_mode(file_path, args).await;
+    }
+
     let file_name = file_path.file_name().unwrap().to_string_lossy().to_string();
     let markdown_input = read_markdown_input(&file_path)?;
-    let html_output = render_markdown_to_html(&markdown_input);
+    let (front_matter, html_output) = render_document(&markdown_input);
     let style = read_style_css();
     let fonts = read_fonts();
-    let (tx, _) = broadcast::channel::<()>(100);
+    let (tx, _) = broadcast::channel::<ReloadEvent>(100);
     let app_state = Arc::new(AppState {
         html_content: Arc::new(RwLock::new(html_output)),
+        front_matter: RwLock::new(front_matter),
         css_content: style,
         fonts,
         file_path: file_path.clone(),
         notifier: tx.clone(),
         file_name,
+        compressed: RwLock::new(HashMap::new()),
     });
 
     // Start the file watcher task
@@ -178,6 +267,7 @@ async fn run_server_mode(args: &Args) -> io::Result<()> {
     let state_filter = warp::any().map(move || app_state.clone());
     let html_route = warp::path::end()
         .and(state_filter.clone())
+        .and(warp::header::optional::<String>("accept-encoding"))
         .and_then(serve_html);
 
     let sse_route = warp::path("events")
@@ -196,20 +286,44 @@ async fn run_server_mode(args: &Args) -> io::Result<()> {
         }
     }
 
-    println!("Server running at http://{}:{}", host, args.port);
-    open_in_browser(format!("http://{}:{}", host, args.port));
-
     let address: IpAddr = args
         .host
         .parse()
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let port = find_available_port(address, args.port)?;
 
-    warp::serve(html_route.or(sse_route))
-        .run((address, args.port))
-        .await;
+    println!("Server running at http://{}:{}", host, port);
+    open_in_browser(format!("http://{}:{}", host, port));
+
+    warp::serve(html_route.or(sse_route)).run((address, port)).await;
     Ok(())
 }
 
+/// Finds a port to serve on starting from `requested_port`: `0` asks the OS
+/// to pick a free one, otherwise we probe by binding a `TcpListener` and
+/// walk upward until one is free, rather than handing a taken port straight
+/// to warp and panicking deep in the runtime.
+fn find_available_port(address: IpAddr, requested_port: u16) -> io::Result<u16> {
+    if requested_port == 0 {
+        let listener = std::net::TcpListener::bind((address, 0))?;
+        return Ok(listener.local_addr()?.port());
+    }
+
+    let mut port = requested_port;
+    loop {
+        match std::net::TcpListener::bind((address, port)) {
+            Ok(_) => return Ok(port),
+            Err(_) if port < u16::MAX => port += 1,
+            Err(e) => {
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!("no free port found starting from {}: {}", requested_port, e),
+                ))
+            }
+        }
+    }
+}
+
 fn read_markdown_input(file_path: &PathBuf) -> io::Result<String> {
     let mut file = File::open(&file_path)?;
     let mut content = String::new();
@@ -217,20 +331,423 @@ fn read_markdown_input(file_path: &PathBuf) -> io::Result<String> {
     Ok(content)
 }
 
+/// Shared state for directory mode: a docs/wiki-style browser over every
+/// markdown file under `root`, rather than a single watched file.
+struct DirAppState {
+    root: PathBuf,
+    files: Vec<PathBuf>,
+    rendered: RwLock<HashMap<PathBuf, (Option<FrontMatter>, String)>>,
+    css_content: String,
+    fonts: Fonts,
+    notifier: broadcast::Sender<PathBuf>,
+}
+
+fn discover_markdown_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect_markdown_files(root, root, &mut files);
+    files.sort();
+    files
+}
+
+fn collect_markdown_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        // `read_dir`'s file type doesn't follow symlinks, unlike `Path::is_dir`.
+        // Skip symlinks outright so a link back to an ancestor directory
+        // can't send this into unbounded recursion.
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_symlink() {
+            continue;
+        }
+        let path = entry.path();
+        if file_type.is_dir() {
+            collect_markdown_files(root, &path, files);
+        } else if path.extension().map_or(false, |ext| ext == "md") {
+            if let Ok(relative) = path.strip_prefix(root) {
+                files.push(relative.to_path_buf());
+            }
+        }
+    }
+}
+
+/// Escapes text for safe interpolation into an HTML text node or
+/// double-quoted attribute.
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Percent-encodes a `/`-joined relative path for use in a URL path, leaving
+/// the separators themselves alone.
+fn percent_encode_path(path: &str) -> String {
+    let mut encoded = String::new();
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+/// Quotes `s` as a safe JS string literal (including the surrounding
+/// quotes) for interpolation into an inline `<script>`.
+fn js_string_literal(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '<' => out.push_str("\\u003C"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn render_sidebar(files: &[PathBuf], active: Option<&Path>) -> String {
+    let mut html = String::from("<nav id=\"sidebar\">\n<ul>\n");
+    for file in files {
+        let relative = file.to_string_lossy().replace('\\', "/");
+        let href = format!("/view/{}", percent_encode_path(&relative));
+        let class = if Some(file.as_path()) == active {
+            " class=\"active\""
+        } else {
+            ""
+        };
+        html.push_str(&format!(
+            "<li><a href=\"{}\"{}>{}</a></li>\n",
+            href,
+            class,
+            html_escape(&file.display().to_string())
+        ));
+    }
+    html.push_str("</ul>\n</nav>\n");
+    html
+}
+
+async fn render_dir_page(state: &DirAppState, relative: Option<PathBuf>) -> String {
+    let sidebar = render_sidebar(&state.files, relative.as_deref());
+    let (title, body, style) = match &relative {
+        Some(rel) => {
+            let cached = state.rendered.read().await.get(rel).cloned();
+            let (front_matter, html) = match cached {
+                Some(entry) => entry,
+                None => {
+                    let markdown_input =
+                        read_markdown_input(&state.root.join(rel)).unwrap_or_default();
+                    let entry = render_document(&markdown_input);
+                    state
+                        .rendered
+                        .write()
+                        .await
+                        .insert(rel.clone(), entry.clone());
+                    entry
+                }
+            };
+            let title = resolve_title(&front_matter, &rel.to_string_lossy());
+            let doc_dir = state
+                .root
+                .join(rel)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| state.root.clone());
+            let style = resolve_style(&state.css_content, &front_matter, &doc_dir);
+            (title, html, style)
+        }
+        None => (
+            "omd".to_string(),
+            "<p>Select a file from the sidebar.</p>".to_string(),
+            state.css_content.clone(),
+        ),
+    };
+
+    let content = format!(
+        "{}\n<div id=\"doc-content\">{}</div>",
+        sidebar, body
+    );
+    let active_path = relative
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default();
+    build_dir_full_html(&title, &content, &style, &state.fonts, &active_path)
+}
+
+async fn serve_dir_index(state: Arc<DirAppState>) -> Result<impl warp::Reply, warp::Rejection> {
+    let html = render_dir_page(&state, state.files.first().cloned()).await;
+    Ok(warp::reply::html(html))
+}
+
+async fn serve_dir_view(
+    tail: warp::path::Tail,
+    state: Arc<DirAppState>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let relative = PathBuf::from(tail.as_str());
+    // Only plain path segments are allowed: `..` escapes `root`, and an
+    // absolute segment (e.g. from a `/view//etc/passwd` tail) makes
+    // `PathBuf::join` discard `root` entirely and read outside it.
+    if !relative
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)))
+    {
+        return Err(warp::reject::not_found());
+    }
+    let html = render_dir_page(&state, Some(relative)).await;
+    Ok(warp::reply::html(html))
+}
+
+fn dir_event_stream(rx: broadcast::Receiver<PathBuf>) -> EventStream {
+    let stream = async_stream::stream! {
+        let mut rx = rx;
+        while let Ok(path) = rx.recv().await {
+            yield Ok(sse::Event::default().data(path.to_string_lossy().replace('\\', "/")));
+        }
+    };
+    Box::pin(stream)
+}
+
+fn watch_markdown_directory(state: Arc<DirAppState>) {
+    use notify::{Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode};
+    use std::sync::mpsc::channel;
+
+    enum WatcherType {
+        PollWatcher(PollWatcher),
+        RecommendedWatcher(RecommendedWatcher),
+    }
+
+    let (tx_notify, rx_notify) = channel();
+    let watcher = if check_for_wsl2() {
+        let mut watcher = PollWatcher::new(
+            tx_notify,
+            Config::default().with_poll_interval(Duration::from_millis(500)),
+        )
+        .unwrap();
+        watcher
+            .watch(state.root.as_path(), RecursiveMode::Recursive)
+            .unwrap();
+        WatcherType::PollWatcher(watcher)
+    } else {
+        let mut watcher = RecommendedWatcher::new(tx_notify, Config::default()).unwrap();
+        watcher
+            .watch(state.root.as_path(), RecursiveMode::Recursive)
+            .unwrap();
+        WatcherType::RecommendedWatcher(watcher)
+    };
+
+    for res in rx_notify {
+        match res {
+            Ok(event) => {
+                if let EventKind::Modify(_) = event.kind {
+                    for path in &event.paths {
+                        if path.extension().map_or(false, |ext| ext == "md") {
+                            let Ok(relative) = path.strip_prefix(&state.root) else {
+                                continue;
+                            };
+                            let relative = relative.to_path_buf();
+                            if let Ok(markdown_input) = std::fs::read_to_string(path) {
+                                println!("File changed, updating {}...", relative.display());
+                                let (front_matter, html_output) = render_document(&markdown_input);
+                                let state_clone = state.clone();
+                                let relative_clone = relative.clone();
+                                tokio::spawn(async move {
+                                    state_clone
+                                        .rendered
+                                        .write()
+                                        .await
+                                        .insert(relative_clone.clone(), (front_matter, html_output));
+                                    if let Err(e) = state_clone.notifier.send(relative_clone) {
+                                        eprintln!("Error sending notification: {}", e);
+                                    }
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("watch error: {:?}", e);
+            }
+        }
+    }
+}
+
+async fn run_directory_mode(root: PathBuf, args: &Args) -> io::Result<()> {
+    let files = discover_markdown_files(&root);
+    let style = read_style_css();
+    let fonts = read_fonts();
+    let (tx, _) = broadcast::channel::<PathBuf>(100);
+
+    let dir_state = Arc::new(DirAppState {
+        root: root.clone(),
+        files,
+        rendered: RwLock::new(HashMap::new()),
+        css_content: style,
+        fonts,
+        notifier: tx.clone(),
+    });
+
+    let dir_state_clone = dir_state.clone();
+    tokio::task::spawn_blocking(move || watch_markdown_directory(dir_state_clone));
+
+    let state_filter = warp::any().map(move || dir_state.clone());
+
+    let index_route = warp::path::end()
+        .and(state_filter.clone())
+        .and_then(serve_dir_index);
+
+    let view_route = warp::path("view")
+        .and(warp::path::tail())
+        .and(state_filter.clone())
+        .and_then(serve_dir_view);
+
+    let sse_route = warp::path("events")
+        .and(warp::get())
+        .and(state_filter.clone())
+        .map(|state: Arc<DirAppState>| {
+            let rx = state.notifier.subscribe();
+            let stream = dir_event_stream(rx);
+            warp::sse::reply(stream)
+        });
+
+    let mut host = args.host.clone();
+    if args.host == "0.0.0.0" {
+        if let Ok(local_ip_address) = local_ip() {
+            host = local_ip_address.to_string()
+        }
+    }
+
+    let address: IpAddr = args
+        .host
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let port = find_available_port(address, args.port)?;
+
+    println!(
+        "Serving {} as a docs browser at http://{}:{}",
+        root.display(),
+        host,
+        port
+    );
+    open_in_browser(format!("http://{}:{}", host, port));
+
+    warp::serve(index_route.or(view_route).or(sse_route))
+        .run((address, port))
+        .await;
+    Ok(())
+}
+
+static SYNTAX_SET: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+static THEME_SET: Lazy<ThemeSet> = Lazy::new(ThemeSet::load_defaults);
+
+fn theme() -> &'static Theme {
+    &THEME_SET.themes["InspiredGitHub"]
+}
+
+fn highlight_code_block(lang: &str, code: &str) -> String {
+    let syntax = SYNTAX_SET
+        .find_syntax_by_token(lang)
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, theme());
+
+    let mut body = String::new();
+    for line in code.lines() {
+        let ranges = match highlighter.highlight_line(line, &SYNTAX_SET) {
+            Ok(ranges) => ranges,
+            Err(_) => return format!("<pre><code>{}</code></pre>", html_escape(code)),
+        };
+        let highlighted = styled_line_to_highlighted_html(&ranges[..], IncludeBackground::No)
+            .unwrap_or_else(|_| html_escape(line));
+        body.push_str(&highlighted);
+        body.push('\n');
+    }
+    format!("<pre class=\"code-block\"><code>{}</code></pre>", body)
+}
+
+/// Recognized front-matter keys. Anything else in the block is ignored.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct FrontMatter {
+    title: Option<String>,
+    theme: Option<String>,
+    css: Option<String>,
+}
+
+/// Strips a leading `+++...+++` (TOML) or `---...---` (YAML) front-matter
+/// block and parses it, passing the document through unchanged when none is
+/// present.
+fn strip_front_matter(input: &str) -> (Option<FrontMatter>, &str) {
+    if let Some(rest) = input.strip_prefix("+++\n") {
+        if let Some(end) = rest.find("\n+++") {
+            let body = rest[end + 4..].strip_prefix('\n').unwrap_or(&rest[end + 4..]);
+            return (toml::from_str(&rest[..end]).ok(), body);
+        }
+    } else if let Some(rest) = input.strip_prefix("---\n") {
+        if let Some(end) = rest.find("\n---") {
+            let body = rest[end + 4..].strip_prefix('\n').unwrap_or(&rest[end + 4..]);
+            return (serde_yaml::from_str(&rest[..end]).ok(), body);
+        }
+    }
+    (None, input)
+}
+
+/// Bundled light/dark stylesheets a document's front matter can opt into via
+/// `theme = "dark"`.
+fn read_theme_css(theme: &str) -> Option<&'static str> {
+    match theme {
+        "dark" => Some(include_str!("./themes/dark.css")),
+        "light" => Some(include_str!("./themes/light.css")),
+        _ => None,
+    }
+}
+
+fn render_document(markdown_input: &str) -> (Option<FrontMatter>, String) {
+    let (front_matter, body) = strip_front_matter(markdown_input);
+    (front_matter, render_markdown_to_html(body))
+}
+
 fn render_markdown_to_html(markdown_input: &str) -> String {
     let options = Options::all();
 
     let parser = MdParser::new_ext(&markdown_input, options);
     let mut html_output = String::new();
-    html::push_html(
-        &mut html_output,
-        parser.map(|event| match event {
-            Event::SoftBreak => Event::Html("<br>".into()),
-            Event::InlineMath(s) => Event::Html(render_inline_latex_to_html(s).into()),
-            Event::DisplayMath(s) => Event::Html(render_display_latex_to_html(s).into()),
-            _ => event,
-        }),
-    );
+
+    let mut events = Vec::new();
+    let mut fenced_lang: Option<String> = None;
+    let mut fenced_buffer = String::new();
+
+    for event in parser {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) => {
+                fenced_lang = Some(lang.to_string());
+                fenced_buffer.clear();
+            }
+            Event::Text(text) if fenced_lang.is_some() => {
+                fenced_buffer.push_str(&text);
+            }
+            Event::End(Tag::CodeBlock(_)) if fenced_lang.is_some() => {
+                let lang = fenced_lang.take().unwrap();
+                let highlighted = highlight_code_block(&lang, &fenced_buffer);
+                events.push(Event::Html(highlighted.into()));
+            }
+            Event::SoftBreak => events.push(Event::Html("<br>".into())),
+            Event::InlineMath(s) => events.push(Event::Html(render_inline_latex_to_html(s).into())),
+            Event::DisplayMath(s) => events.push(Event::Html(render_display_latex_to_html(s).into())),
+            other => events.push(other),
+        }
+    }
+
+    html::push_html(&mut html_output, events.into_iter());
     html_output
 }
 
@@ -270,6 +787,79 @@ fn read_style_css() -> String {
     css_file
 }
 
+/// Folds a document's front-matter `theme` and `css` overrides into the
+/// base stylesheet, in that order so a user stylesheet can win over the
+/// bundled theme. A relative `css` path is resolved against `doc_dir` (the
+/// document's own directory), not the process's current directory, since
+/// `omd` is typically invoked from elsewhere.
+fn resolve_style(base_style: &str, front_matter: &Option<FrontMatter>, doc_dir: &Path) -> String {
+    let mut style = base_style.to_string();
+    let Some(front_matter) = front_matter else {
+        return style;
+    };
+    if let Some(theme) = &front_matter.theme {
+        if let Some(theme_css) = read_theme_css(theme) {
+            style.push_str(theme_css);
+        }
+    }
+    if let Some(css_path) = &front_matter.css {
+        let css_path = Path::new(css_path);
+        // Same class of bug as chunk0-4's `/view` traversal: an absolute or
+        // `..`-containing path would make `join` discard `doc_dir` and read
+        // arbitrary files. Only allow plain relative paths under `doc_dir`.
+        let is_safe = css_path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)));
+        if is_safe {
+            if let Ok(extra) = std::fs::read_to_string(doc_dir.join(css_path)) {
+                style.push_str(&extra);
+            }
+        }
+    }
+    style
+}
+
+fn resolve_title(front_matter: &Option<FrontMatter>, fallback: &str) -> String {
+    front_matter
+        .as_ref()
+        .and_then(|fm| fm.title.clone())
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// Vendored copy of pulldown-latex's stylesheet and math fonts, embedded so
+/// exported HTML never reaches out to `cdn.jsdelivr.net`.
+struct MathAssets {
+    css: String,
+}
+
+/// The vendored CSS only needs decoding/rewriting once; every caller after
+/// the first reuses this, the same way `SYNTAX_SET`/`THEME_SET` do above.
+static MATH_ASSETS: Lazy<MathAssets> = Lazy::new(read_math_assets);
+
+fn read_math_assets() -> MathAssets {
+    let css_template = include_str!("./vendor/pulldown-latex/styles.min.css");
+    let font_regular = encode(include_bytes!(
+        "./vendor/pulldown-latex/font/pulldown-latex-math-regular.woff2"
+    ));
+    let font_bold = encode(include_bytes!(
+        "./vendor/pulldown-latex/font/pulldown-latex-math-bold.woff2"
+    ));
+
+    // The stylesheet references its fonts by relative path; swap those for
+    // `data:` URIs so the whole thing is self-contained.
+    let css = css_template
+        .replace(
+            "url(\"./font/pulldown-latex-math-regular.woff2\")",
+            &format!("url(data:font/woff2;charset=utf-8;base64,{})", font_regular),
+        )
+        .replace(
+            "url(\"./font/pulldown-latex-math-bold.woff2\")",
+            &format!("url(data:font/woff2;charset=utf-8;base64,{})", font_bold),
+        );
+
+    MathAssets { css }
+}
+
 struct Fonts {
     font_regular: String,
     font_medium: String,
@@ -288,11 +878,20 @@ fn read_fonts() -> Fonts {
 
 struct AppState {
     html_content: Arc<RwLock<String>>,
+    front_matter: RwLock<Option<FrontMatter>>,
     css_content: String,
     fonts: Fonts,
     file_path: PathBuf,
-    notifier: broadcast::Sender<()>,
+    notifier: broadcast::Sender<ReloadEvent>,
     file_name: String,
+    /// Gzip/brotli copies of the last `build_full_html` output, keyed by
+    /// encoding name ("gzip"/"br") and paired with the exact HTML that was
+    /// compressed, so a cache hit requires matching content and not just a
+    /// matching encoding. `watch_markdown_file` eagerly clears this when the
+    /// markdown file changes, but `resolve_style` also reads an external
+    /// front-matter `css:` file fresh on every request -- the content check
+    /// is what catches that file changing without the markdown changing.
+    compressed: RwLock<HashMap<String, (String, Vec<u8>)>>,
 }
 
 fn watch_markdown_file(app_state: Arc<AppState>) {
@@ -336,13 +935,24 @@ fn watch_markdown_file(app_state: Arc<AppState>) {
                     println!("File changed, updating content...");
                     match std::fs::read_to_string(&app_state.file_path) {
                         Ok(markdown_input) => {
-                            let html_output = render_markdown_to_html(&markdown_input);
+                            let (front_matter, html_output) = render_document(&markdown_input);
                             // Use a synchronous write method or spawn a Tokio task to handle async operations
                             let app_state_clone = app_state.clone();
                             tokio::spawn(async move {
-                                let mut html_content = app_state_clone.html_content.write().await;
-                                *html_content = html_output;
-                                if let Err(e) = app_state_clone.notifier.send(()) {
+                                let reload_event = {
+                                    let mut html_content =
+                                        app_state_clone.html_content.write().await;
+                                    let event = if html_output.len() <= DOM_PATCH_SIZE_LIMIT {
+                                        diff_event(&html_content, &html_output)
+                                    } else {
+                                        ReloadEvent::Reload
+                                    };
+                                    *html_content = html_output;
+                                    event
+                                };
+                                *app_state_clone.front_matter.write().await = front_matter;
+                                app_state_clone.compressed.write().await.clear();
+                                if let Err(e) = app_state_clone.notifier.send(reload_event) {
                                     eprintln!("Error sending notification: {}", e);
                                 }
                             });
@@ -376,16 +986,102 @@ async fn sse_handler(app_state: Arc<AppState>) -> Result<impl warp::Reply, warp:
     ))
 }
 
-async fn serve_html(app_state: Arc<AppState>) -> Result<impl warp::Reply, warp::Rejection> {
+async fn serve_html(
+    app_state: Arc<AppState>,
+    accept_encoding: Option<String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
     let html_content = app_state.html_content.read().await;
+    let front_matter = app_state.front_matter.read().await.clone();
+    let title = resolve_title(&front_matter, &app_state.file_name);
+    let doc_dir = app_state.file_path.parent().unwrap_or(Path::new("."));
+    let style = resolve_style(&app_state.css_content, &front_matter, doc_dir);
     let full_html = build_full_html(
-        &app_state.file_name,
+        &title,
         &html_content,
-        &app_state.css_content,
+        &style,
         &app_state.fonts,
         true, // Enable live reload script
     );
-    Ok(warp::reply::html(full_html))
+    drop(html_content);
+
+    let (body, encoding) =
+        compress_for_client(&app_state, full_html, &accept_encoding.unwrap_or_default()).await;
+    Ok(html_response(body, encoding))
+}
+
+/// Wraps gzip/brotli so the caller gets back plain bytes either way.
+async fn gzip_compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    use async_compression::tokio::write::GzipEncoder;
+    use tokio::io::AsyncWriteExt;
+
+    let mut encoder = GzipEncoder::new(Vec::new());
+    encoder.write_all(data).await?;
+    encoder.shutdown().await?;
+    Ok(encoder.into_inner())
+}
+
+async fn brotli_compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    use async_compression::tokio::write::BrotliEncoder;
+    use tokio::io::AsyncWriteExt;
+
+    let mut encoder = BrotliEncoder::new(Vec::new());
+    encoder.write_all(data).await?;
+    encoder.shutdown().await?;
+    Ok(encoder.into_inner())
+}
+
+/// Picks the best encoding the client advertised, compressing (and caching)
+/// `full_html` on demand. Falls back to the uncompressed bytes if neither
+/// encoding is accepted or compression fails.
+async fn compress_for_client(
+    app_state: &AppState,
+    full_html: String,
+    accept_encoding: &str,
+) -> (Vec<u8>, Option<&'static str>) {
+    let encoding = if accept_encoding.contains("br") {
+        "br"
+    } else if accept_encoding.contains("gzip") {
+        "gzip"
+    } else {
+        return (full_html.into_bytes(), None);
+    };
+
+    if let Some((source, cached)) = app_state.compressed.read().await.get(encoding) {
+        if *source == full_html {
+            return (cached.clone(), Some(encoding));
+        }
+    }
+
+    let compressed = if encoding == "br" {
+        brotli_compress(full_html.as_bytes()).await
+    } else {
+        gzip_compress(full_html.as_bytes()).await
+    };
+
+    match compressed {
+        Ok(bytes) => {
+            app_state
+                .compressed
+                .write()
+                .await
+                .insert(encoding.to_string(), (full_html.clone(), bytes.clone()));
+            (bytes, Some(encoding))
+        }
+        Err(e) => {
+            eprintln!("Error compressing response: {}", e);
+            (full_html.into_bytes(), None)
+        }
+    }
+}
+
+fn html_response(body: Vec<u8>, encoding: Option<&'static str>) -> impl warp::Reply {
+    let mut builder = warp::http::Response::builder()
+        .header("Content-Type", "text/html; charset=utf-8")
+        .header("Vary", "Accept-Encoding");
+    if let Some(encoding) = encoding {
+        builder = builder.header("Content-Encoding", encoding);
+    }
+    builder.body(body).unwrap()
 }
 
 fn build_full_html(
@@ -396,22 +1092,146 @@ fn build_full_html(
     enable_reload: bool,
 ) -> String {
     let reload_script = if enable_reload {
-        r#"
+        // `__omdBody` mirrors exactly what we last rendered into
+        // `document.body`; patches splice against this tracked string
+        // rather than `document.body.innerHTML`, which the browser may
+        // re-serialize differently than what we set.
+        let initial_body = encode(html_output.as_bytes());
+        format!(
+            r#"
         <script>
+            function b64DecodeUnicode(str) {{
+                return decodeURIComponent(Array.prototype.map.call(atob(str), function(c) {{
+                    return '%' + ('00' + c.charCodeAt(0).toString(16)).slice(-2);
+                }}).join(''));
+            }}
+            var __omdBody = b64DecodeUnicode("{}");
             var evtSource = new EventSource("/events");
-            evtSource.onmessage = function(e) {
-                if (e.data === "reload") {
-                    location.reload();
-                }
-            };
+            evtSource.addEventListener("patch", function(e) {{
+                var scrollY = window.scrollY;
+                var data = JSON.parse(e.data);
+                // prefix/suffix are UTF-8 byte offsets, not JS UTF-16 code
+                // units, so splice on bytes rather than on the JS string.
+                var currentBytes = new TextEncoder().encode(__omdBody);
+                var middleBytes = Uint8Array.from(atob(data.middle), function(c) {{
+                    return c.charCodeAt(0);
+                }});
+                var suffixStart = currentBytes.length - data.suffix;
+                var newBytes = new Uint8Array(data.prefix + middleBytes.length + data.suffix);
+                newBytes.set(currentBytes.subarray(0, data.prefix), 0);
+                newBytes.set(middleBytes, data.prefix);
+                newBytes.set(currentBytes.subarray(suffixStart), data.prefix + middleBytes.length);
+                __omdBody = new TextDecoder().decode(newBytes);
+                document.body.innerHTML = __omdBody;
+                relocateFootnotes();
+                window.scrollTo(0, scrollY);
+            }});
+            evtSource.addEventListener("reload", function(e) {{
+                location.reload();
+            }});
         </script>
-        "#
+        "#,
+            initial_body
+        )
     } else {
-        ""
+        String::new()
     };
 
-    // Can you make cargo do this?
-    let pulldown_latex_version = "0.7.0";
+    let math_assets = &*MATH_ASSETS;
+    let math_css = &math_assets.css;
+
+    format!(
+        r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <link rel="icon" href="data:image/x-icon;base64,{}">
+    <style>
+        {math_css}
+    </style>
+    <script>
+        function relocateFootnotes() {{
+            const footnotes = document.querySelectorAll('.footnote-definition');
+            if (footnotes.length > 0) {{
+                const container = document.createElement('div');
+                container.id = 'footnote-container';
+                footnotes.forEach(footnote => container.appendChild(footnote));
+                document.body.appendChild(container);
+            }}
+        }}
+        document.addEventListener('DOMContentLoaded', relocateFootnotes);
+    </script>
+    <style>
+        @font-face {{
+            font-family: 'Oswald';
+            src: url(data:font/truetype;charset=utf-8;base64,{}) format('truetype');
+            font-weight: 400;
+            font-style: normal;
+        }}
+        @font-face {{
+            font-family: 'Oswald';
+            src: url(data:font/truetype;charset=utf-8;base64,{}) format('truetype');
+            font-weight: 700;
+            font-style: normal;
+        }}
+        @font-face {{
+            font-family: 'Oswald';
+            src: url(data:font/truetype;charset=utf-8;base64,{}) format('truetype');
+            font-weight: 300;
+            font-style: normal;
+        }}
+        {}
+    </style>
+    <title>
+        {}
+    </title>
+</head>
+<body>
+    {}
+    {}
+</body>
+</html>
+"#,
+        fonts.favicon,
+        fonts.font_regular,
+        fonts.font_medium,
+        fonts.font_light,
+        style,
+        file_name,
+        html_output,
+        reload_script
+    )
+}
+
+/// Same wrapper as `build_full_html`, but for directory mode: adds the
+/// sidebar layout and only reloads when the SSE-reported path matches the
+/// page the client currently has open.
+fn build_dir_full_html(
+    file_name: &str,
+    html_output: &str,
+    style: &str,
+    fonts: &Fonts,
+    active_path: &str,
+) -> String {
+    let reload_script = format!(
+        r#"
+        <script>
+            var evtSource = new EventSource("/events");
+            var activePath = {};
+            evtSource.onmessage = function(e) {{
+                if (e.data === activePath) {{
+                    location.reload();
+                }}
+            }};
+        </script>
+        "#,
+        js_string_literal(active_path)
+    );
+
+    let math_assets = &*MATH_ASSETS;
+    let math_css = &math_assets.css;
 
     format!(
         r#"
@@ -421,10 +1241,11 @@ fn build_full_html(
     <meta charset="UTF-8">
     <meta name="viewport" content="width=device-width, initial-scale=1.0">
     <link rel="icon" href="data:image/x-icon;base64,{}">
-    <link rel="stylesheet" href="https://cdn.jsdelivr.net/gh/carloskiki/pulldown-latex@{pulldown_latex_version}/styles.min.css">
-    <link rel="preload" href="https://cdn.jsdelivr.net/gh/carloskiki/pulldown-latex@{pulldown_latex_version}/font/" as="font" crossorigin="anonymous">
+    <style>
+        {math_css}
+    </style>
     <script>
-        document.addEventListener('DOMContentLoaded', function() {{
+        function relocateFootnotes() {{
             const footnotes = document.querySelectorAll('.footnote-definition');
             if (footnotes.length > 0) {{
                 const container = document.createElement('div');
@@ -432,7 +1253,8 @@ fn build_full_html(
                 footnotes.forEach(footnote => container.appendChild(footnote));
                 document.body.appendChild(container);
             }}
-        }});
+        }}
+        document.addEventListener('DOMContentLoaded', relocateFootnotes);
     </script>
     <style>
         @font-face {{
@@ -453,6 +1275,19 @@ fn build_full_html(
             font-weight: 300;
             font-style: normal;
         }}
+        body {{
+            display: flex;
+        }}
+        #sidebar {{
+            width: 240px;
+            flex-shrink: 0;
+            overflow-y: auto;
+            border-right: 1px solid #ddd;
+        }}
+        #doc-content {{
+            flex-grow: 1;
+            min-width: 0;
+        }}
         {}
     </style>
     <title>